@@ -0,0 +1,67 @@
+//! Incremental delta snapshots, built on SQLite's session extension (the
+//! `session` feature surface rusqlite ships). Instead of re-vacuuming the
+//! whole database on every checkpoint, a caller can keep a single base
+//! `vacuum into` snapshot plus a stream of small changesets recorded here.
+
+use std::io::Cursor;
+use std::panic::RefUnwindSafe;
+
+use rusqlite::session::{ConflictAction, ConflictType, Session as RawSession};
+use rusqlite::Connection;
+
+/// Tracks mutations to attached tables on a live connection and emits them
+/// as a compact changeset blob.
+pub struct Session<'conn> {
+    inner: RawSession<'conn>,
+}
+
+impl<'conn> Session<'conn> {
+    /// Attach a session to `conn` and start recording changes to `table`.
+    #[tracing::instrument(level = "debug", name = "Attaching session to table", skip(conn))]
+    pub fn attach(conn: &'conn Connection, table: &str) -> rusqlite::Result<Self> {
+        let mut inner = RawSession::new(conn)?;
+        inner.attach(Some(table))?;
+
+        Ok(Self { inner })
+    }
+
+    /// Emit a changeset covering every change recorded since the session was
+    /// attached. The session is not reset by this call, so each later call
+    /// again includes the changes already returned by earlier ones.
+    #[tracing::instrument(level = "debug", name = "Emitting changeset", skip(self))]
+    pub fn changeset(&mut self) -> rusqlite::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.inner.changeset_strm(&mut buf)?;
+
+        Ok(buf)
+    }
+}
+
+/// What to do when replaying `changeset` hits a row that no longer matches
+/// the state it was recorded against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Replace,
+    Skip,
+    Abort,
+}
+
+/// Replay a changeset recorded by [`Session`] onto `conn`, which is expected
+/// to have been opened from a prior `vacuum into` snapshot. `on_conflict` is
+/// consulted whenever a row in the changeset no longer matches the target.
+#[tracing::instrument(level = "debug", name = "Applying changeset", skip(conn, changeset, on_conflict))]
+pub fn apply_changeset(
+    conn: &Connection,
+    changeset: &[u8],
+    on_conflict: impl Fn(ConflictType) -> Resolution + Send + RefUnwindSafe + 'static,
+) -> rusqlite::Result<()> {
+    conn.apply_strm(
+        &mut Cursor::new(changeset),
+        Some(|_table: &str| true),
+        move |conflict_type, _item| match on_conflict(conflict_type) {
+            Resolution::Replace => ConflictAction::SQLITE_CHANGESET_REPLACE,
+            Resolution::Skip => ConflictAction::SQLITE_CHANGESET_OMIT,
+            Resolution::Abort => ConflictAction::SQLITE_CHANGESET_ABORT,
+        },
+    )
+}
@@ -0,0 +1,20 @@
+//! Opening a SQLCipher-encrypted destination database produced by an
+//! encrypted `vacuum into` (see the `sqlcipher` feature on [`crate::vacuum_into`]).
+//!
+//! This module, and the tests that exercise it, only compile with the
+//! `sqlcipher` feature enabled; run `cargo test --features sqlcipher` to
+//! cover the encrypted path, since a default `cargo test` skips it entirely.
+
+use std::path::Path;
+
+use rusqlite::Connection;
+
+/// Open `path` and apply the SQLCipher key immediately, before any other
+/// statement runs against the connection.
+#[tracing::instrument(level = "debug", name = "Opening encrypted database", skip(key))]
+pub fn open_encrypted(path: &Path, key: &str) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "key", key)?;
+
+    Ok(conn)
+}
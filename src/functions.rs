@@ -0,0 +1,80 @@
+//! Registering custom SQL scalar/aggregate functions on the source
+//! connection before it is vacuumed (wrapping rusqlite's `functions`
+//! support), so digests or derived columns can be computed during the same
+//! scan that feeds the vacuum instead of pulling every row into Rust first.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+use rusqlite::functions::{Aggregate, Context, FunctionFlags};
+use rusqlite::types::{ToSql, ToSqlOutput};
+use rusqlite::Connection;
+
+fn flags(deterministic: bool) -> FunctionFlags {
+    if deterministic {
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC
+    } else {
+        FunctionFlags::SQLITE_UTF8
+    }
+}
+
+/// Register a scalar SQL function named `name`, taking `nargs` arguments,
+/// calling `func` for every invocation.
+#[tracing::instrument(level = "debug", name = "Registering scalar function", skip(conn, func))]
+pub fn register_scalar<F>(
+    conn: &Connection,
+    name: &str,
+    nargs: i32,
+    deterministic: bool,
+    func: F,
+) -> rusqlite::Result<()>
+where
+    F: Fn(&Context<'_>) -> rusqlite::Result<ToSqlOutput<'static>> + Send + Sync + UnwindSafe + 'static,
+{
+    conn.create_scalar_function(name, nargs, flags(deterministic), move |ctx| func(ctx))
+}
+
+/// Register an aggregate SQL function named `name` (e.g. a rolling
+/// `checksum(...)`), taking `nargs` arguments per row. `S` is the
+/// per-group accumulator state and `T` the final output type.
+#[tracing::instrument(level = "debug", name = "Registering aggregate function", skip(conn, aggregate))]
+pub fn register_aggregate<A, S, T>(
+    conn: &Connection,
+    name: &str,
+    nargs: i32,
+    deterministic: bool,
+    aggregate: A,
+) -> rusqlite::Result<()>
+where
+    A: Aggregate<S, T> + 'static,
+    S: 'static + RefUnwindSafe + UnwindSafe,
+    T: ToSql,
+{
+    conn.create_aggregate_function(name, nargs, flags(deterministic), aggregate)
+}
+
+/// A rolling-XOR `checksum(col)` aggregate: folds every row's column value
+/// into a single `u64` accumulator, independent of row order. Registered via
+/// [`register_aggregate`] the same way a scalar closure is registered via
+/// [`register_scalar`].
+pub struct Checksum;
+
+impl Aggregate<u64, i64> for Checksum {
+    fn init(&self, _ctx: &mut Context<'_>) -> rusqlite::Result<u64> {
+        Ok(0)
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, acc: &mut u64) -> rusqlite::Result<()> {
+        let value: String = ctx.get(0)?;
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        *acc ^= hasher.finish();
+
+        Ok(())
+    }
+
+    fn finalize(&self, _ctx: &mut Context<'_>, acc: Option<u64>) -> rusqlite::Result<i64> {
+        Ok(acc.unwrap_or(0) as i64)
+    }
+}
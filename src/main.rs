@@ -4,6 +4,14 @@ use sqlx::{ConnectOptions, Executor};
 use tempfile::NamedTempFile;
 use tracing_test::traced_test;
 
+mod changeset;
+mod digest;
+#[cfg(feature = "sqlcipher")]
+mod encrypted;
+mod functions;
+mod row;
+mod snapshot;
+
 static CREATE_TABLE: &str = r#"
   create table test (id integer primary key, name text);
   insert into test (name) values ('hello');
@@ -18,13 +26,38 @@ struct Test {
     name: String,
 }
 
+impl row::FromRow for Test {
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        let (id, name) = row::row_extract(row)?;
+
+        Ok(Self { id, name })
+    }
+}
+
 #[derive(Debug, sqlx::FromRow)]
 struct Table {
     name: String,
 }
 
+/// Build a `VACUUM INTO` statement. `passphrase` is only honored when built
+/// with the `sqlcipher` feature, in which case it's emitted as a `KEY`
+/// clause so the destination is written as a SQLCipher-encrypted database;
+/// without that feature a passphrase is refused rather than silently
+/// producing an unencrypted file.
 #[tracing::instrument(level = "debug", name = "Creating vacuum statement")]
-fn vacuum_into(db_str: &str) -> String {
+fn vacuum_into(db_str: &str, passphrase: Option<&str>) -> String {
+    #[cfg(feature = "sqlcipher")]
+    if let Some(key) = passphrase {
+        return dbg!(format!("vacuum into '{db_str}' key '{key}'"));
+    }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    assert!(
+        passphrase.is_none(),
+        "a passphrase was given but the `sqlcipher` feature is disabled; \
+         refusing to silently emit an unencrypted vacuum"
+    );
+
     dbg!(format!("vacuum into '{db_str}'"))
 }
 
@@ -49,7 +82,7 @@ async fn sqlx_with_shared_cache_connection() -> anyhow::Result<()> {
 
     assert_eq!(things.len(), 2);
 
-    let vacuum_statement = vacuum_into(db_path);
+    let vacuum_statement = vacuum_into(db_path, None);
 
     tracing::info!(name = "Vacuuming into new db", path = db_path);
     conn.execute(vacuum_statement.as_ref()).await?;
@@ -107,7 +140,7 @@ async fn sqlx_with_pooled_connection() -> anyhow::Result<()> {
 
     assert_eq!(things.len(), 2);
 
-    let vacuum_statement = vacuum_into(db_path);
+    let vacuum_statement = vacuum_into(db_path, None);
 
     tracing::info!(name = "Vacuuming into new db", path = db_path);
     pool.execute(vacuum_statement.as_ref()).await?;
@@ -158,12 +191,7 @@ async fn rusqlite() -> anyhow::Result<()> {
     tracing::info!(name = "Selecting all data from memory table", SELECT_ALL);
     let mut statement = conn.prepare(SELECT_ALL).unwrap();
     let things: Vec<Test> = statement
-        .query_map([], |row| {
-            Ok(Test {
-                id: row.get(0).expect(""),
-                name: row.get(1).expect(""),
-            })
-        })
+        .query_map([], |row| row::FromRow::from_row(row))
         .unwrap()
         .into_iter()
         .map(|r| r.unwrap())
@@ -184,12 +212,7 @@ async fn rusqlite() -> anyhow::Result<()> {
 
     tracing::info!(name = "Selecting all data from temp db");
     let stored_things: Vec<Test> = statement
-        .query_map([], |row| {
-            Ok(Test {
-                id: row.get(0).expect(""),
-                name: row.get(1).expect(""),
-            })
-        })
+        .query_map([], |row| row::FromRow::from_row(row))
         .unwrap()
         .into_iter()
         .map(|r| r.unwrap())
@@ -209,6 +232,203 @@ async fn test_rusqlite() -> anyhow::Result<()> {
     rusqlite().await
 }
 
+#[cfg(feature = "sqlcipher")]
+#[tracing::instrument(level = "debug", name = "Running encrypted vacuum demo")]
+async fn encrypted_demo() -> anyhow::Result<()> {
+    let new_db = NamedTempFile::new()?;
+    let db_path: &str = new_db.as_ref().as_os_str().try_into()?;
+    let conn = rusqlite::Connection::open_in_memory()?;
+
+    tracing::info!(name = "Creating table and inserting data", CREATE_TABLE);
+    conn.execute_batch(CREATE_TABLE)?;
+
+    tracing::info!(name = "Vacuuming into encrypted db", db_path);
+    let vacuum_statement = vacuum_into(db_path, Some("correct horse battery staple"));
+    conn.execute(vacuum_statement.as_ref(), [])?;
+
+    tracing::info!(name = "Opening encrypted db without the key should fail to read");
+    let unkeyed = rusqlite::Connection::open(new_db.as_ref())?;
+    assert!(unkeyed.prepare(SELECT_ALL).is_err());
+
+    tracing::info!(name = "Opening encrypted db with the key should succeed");
+    let keyed = encrypted::open_encrypted(new_db.as_ref(), "correct horse battery staple")?;
+    let mut statement = keyed.prepare(SELECT_ALL)?;
+    let stored_things: Vec<Test> = statement
+        .query_map([], |row| row::FromRow::from_row(row))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    assert_eq!(stored_things.len(), 2);
+
+    Ok(())
+}
+
+#[cfg(feature = "sqlcipher")]
+#[traced_test]
+#[tokio::test]
+async fn test_encrypted_vacuum_requires_key() -> anyhow::Result<()> {
+    encrypted_demo().await
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_row_fingerprint_scalar_function() -> anyhow::Result<()> {
+    let conn = rusqlite::Connection::open_in_memory()?;
+
+    tracing::info!(name = "Creating table and inserting data", CREATE_TABLE);
+    conn.execute_batch(CREATE_TABLE)?;
+
+    tracing::info!(name = "Registering row_fingerprint scalar function");
+    functions::register_scalar(&conn, "row_fingerprint", 1, true, |ctx| {
+        let name: String = ctx.get(0)?;
+        Ok(rusqlite::types::ToSqlOutput::from(name.len() as i64))
+    })?;
+
+    tracing::info!(name = "Computing fingerprints inside the query");
+    let mut statement = conn.prepare("select row_fingerprint(name) from test")?;
+    let fingerprints: Vec<i64> = statement
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    assert_eq!(fingerprints, vec!["hello".len() as i64, "world".len() as i64]);
+
+    Ok(())
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_checksum_aggregate_function() -> anyhow::Result<()> {
+    let conn = rusqlite::Connection::open_in_memory()?;
+
+    tracing::info!(name = "Creating table and inserting data", CREATE_TABLE);
+    conn.execute_batch(CREATE_TABLE)?;
+
+    tracing::info!(name = "Registering checksum aggregate function");
+    functions::register_aggregate(&conn, "checksum", 1, true, functions::Checksum)?;
+
+    tracing::info!(name = "Computing a checksum inside the query");
+    let checksum: i64 = conn.query_row("select checksum(name) from test", [], |row| row.get(0))?;
+    let rechecksum: i64 = conn.query_row("select checksum(name) from test", [], |row| row.get(0))?;
+
+    assert_eq!(checksum, rechecksum);
+    assert_ne!(checksum, 0);
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "debug", name = "Running online backup snapshot demo")]
+async fn snapshot_demo() -> anyhow::Result<()> {
+    let new_db = NamedTempFile::new()?;
+    let conn = rusqlite::Connection::open_in_memory()?;
+
+    tracing::info!(name = "Creating table and inserting data", CREATE_TABLE);
+    conn.execute_batch(CREATE_TABLE)?;
+
+    tracing::info!(name = "Snapshotting source into new db via online backup");
+    snapshot::snapshot_into(&conn, new_db.as_ref(), 1, None, |progress| {
+        tracing::debug!(name = "Backup step complete", ?progress);
+    })?;
+
+    let file_conn = rusqlite::Connection::open(new_db.as_ref())?;
+    let mut statement = file_conn.prepare(SELECT_ALL)?;
+
+    tracing::info!(name = "Selecting all data from snapshot");
+    let stored_things: Vec<Test> = statement
+        .query_map([], |row| row::FromRow::from_row(row))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    assert_eq!(stored_things.len(), 2);
+
+    Ok(())
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_snapshot_into() -> anyhow::Result<()> {
+    snapshot_demo().await
+}
+
+#[tracing::instrument(level = "debug", name = "Running changeset demo")]
+async fn changeset_demo() -> anyhow::Result<()> {
+    let conn = rusqlite::Connection::open_in_memory()?;
+
+    tracing::info!(name = "Creating table and inserting data", CREATE_TABLE);
+    conn.execute_batch(CREATE_TABLE)?;
+
+    let base_db = NamedTempFile::new()?;
+    let base_path: &str = base_db.as_ref().as_os_str().try_into()?;
+
+    tracing::info!(name = "Taking base snapshot via vacuum into", path = base_path);
+    let vacuum_statement = vacuum_into(base_path, None);
+    conn.execute(vacuum_statement.as_ref(), [])?;
+
+    tracing::info!(name = "Attaching session and recording a delta");
+    let mut session = changeset::Session::attach(&conn, "test")?;
+    conn.execute("insert into test (name) values ('changeset')", [])?;
+    let delta = session.changeset()?;
+
+    tracing::info!(name = "Applying changeset onto the base snapshot");
+    let base_conn = rusqlite::Connection::open(base_db.as_ref())?;
+    changeset::apply_changeset(&base_conn, &delta, |_conflict| changeset::Resolution::Replace)?;
+
+    let full_db = NamedTempFile::new()?;
+    let full_path: &str = full_db.as_ref().as_os_str().try_into()?;
+
+    tracing::info!(name = "Taking a fresh full vacuum for comparison", path = full_path);
+    let vacuum_statement = vacuum_into(full_path, None);
+    conn.execute(vacuum_statement.as_ref(), [])?;
+
+    let full_conn = rusqlite::Connection::open(full_db.as_ref())?;
+
+    let mut base_statement = base_conn.prepare(SELECT_ALL)?;
+    let base_rows: Vec<Test> = base_statement
+        .query_map([], |row| row::FromRow::from_row(row))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut full_statement = full_conn.prepare(SELECT_ALL)?;
+    let full_rows: Vec<Test> = full_statement
+        .query_map([], |row| row::FromRow::from_row(row))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    tracing::info!(name = "Comparing base snapshot plus changeset with a fresh full vacuum");
+    assert_eq!(base_rows, full_rows);
+
+    Ok(())
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_changeset_matches_full_vacuum() -> anyhow::Result<()> {
+    changeset_demo().await
+}
+
+#[tracing::instrument(level = "debug", name = "Running content digest demo")]
+async fn digest_demo() -> anyhow::Result<()> {
+    let new_db = NamedTempFile::new()?;
+    let db_path: &str = new_db.as_ref().as_os_str().try_into()?;
+
+    let conn = digest::Tracked::wrap(rusqlite::Connection::open_in_memory()?);
+
+    tracing::info!(name = "Creating table and inserting data", CREATE_TABLE);
+    conn.execute_batch(CREATE_TABLE)?;
+
+    tracing::info!(name = "Vacuuming into new db", db_path);
+    let vacuum_statement = vacuum_into(db_path, None);
+    conn.execute(vacuum_statement.as_ref(), [])?;
+
+    let dest_conn = rusqlite::Connection::open(new_db.as_ref())?;
+
+    tracing::info!(name = "Comparing tracked digest with a single rescan of the snapshot");
+    assert_eq!(conn.content_digest(), digest::scan_digest(&dest_conn, "test")?);
+
+    Ok(())
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_digest_matches_snapshot() -> anyhow::Result<()> {
+    digest_demo().await
+}
+
 #[traced_test]
 #[tokio::test]
 async fn test_sqlx_with_pools() -> anyhow::Result<()> {
@@ -232,6 +452,12 @@ async fn main() -> anyhow::Result<()> {
     sqlx_with_pooled_connection().await?;
     sqlx_with_shared_cache_connection().await?;
     rusqlite().await?;
+    snapshot_demo().await?;
+    changeset_demo().await?;
+    digest_demo().await?;
+
+    #[cfg(feature = "sqlcipher")]
+    encrypted_demo().await?;
 
     Ok(())
 }
@@ -0,0 +1,35 @@
+//! A driver-agnostic row mapping trait, so the rusqlite path can extract
+//! rows the same way the sqlx paths do via `#[derive(sqlx::FromRow)]`,
+//! instead of hand-writing a `row.get(n)` closure per query.
+
+use rusqlite::types::FromSql;
+use rusqlite::Row;
+
+/// Extracts a typed value from a single rusqlite row.
+pub trait FromRow: Sized {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self>;
+}
+
+macro_rules! impl_from_row_for_tuple {
+    ($($idx:tt : $ty:ident),+) => {
+        impl<$($ty),+> FromRow for ($($ty,)+)
+        where
+            $($ty: FromSql,)+
+        {
+            fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+                Ok(($(row.get($idx)?,)+))
+            }
+        }
+    };
+}
+
+impl_from_row_for_tuple!(0: A);
+impl_from_row_for_tuple!(0: A, 1: B);
+impl_from_row_for_tuple!(0: A, 1: B, 2: C);
+impl_from_row_for_tuple!(0: A, 1: B, 2: C, 3: D);
+
+/// A `query_as`-style helper: extract `T` from a single row, independent of
+/// how many columns it is made of.
+pub fn row_extract<T: FromRow>(row: &Row<'_>) -> rusqlite::Result<T> {
+    T::from_row(row)
+}